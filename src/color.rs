@@ -0,0 +1,84 @@
+//! ANSI color output for stdout, driven by log level.
+
+use std::ops::Range;
+
+const RESET: &str = "\x1b[0m";
+
+/// When to colorize stdout output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Never colorize.
+    #[default]
+    Never,
+    /// Colorize only when stdout is a terminal, to avoid polluting redirected files or pipes.
+    Auto,
+    /// Always colorize.
+    Always,
+}
+impl ColorMode {
+    pub(crate) fn enabled(self, stdout_is_tty: bool) -> bool {
+        match self {
+            Self::Never => false,
+            Self::Auto => stdout_is_tty,
+            Self::Always => true,
+        }
+    }
+}
+
+fn sgr_for(level: log::Level) -> &'static str {
+    match level {
+        log::Level::Error => "\x1b[31m",
+        log::Level::Warn => "\x1b[33m",
+        log::Level::Info => "\x1b[32m",
+        log::Level::Debug | log::Level::Trace => "\x1b[2m",
+    }
+}
+
+/// Wraps each of `ranges` (byte ranges into `line`, in ascending non-overlapping order) in the
+/// SGR code for `level`, leaving the rest of `line` untouched.
+pub(crate) fn colorize(line: &str, level: log::Level, ranges: &[Range<usize>]) -> String {
+    if ranges.is_empty() {
+        return line.to_string();
+    }
+    let sgr = sgr_for(level);
+    let mut out = String::with_capacity(line.len() + ranges.len() * (sgr.len() + RESET.len()));
+    let mut last_end = 0;
+    for range in ranges {
+        out.push_str(&line[last_end..range.start]);
+        out.push_str(sgr);
+        out.push_str(&line[range.clone()]);
+        out.push_str(RESET);
+        last_end = range.end;
+    }
+    out.push_str(&line[last_end..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enabled_follows_mode() {
+        assert!(!ColorMode::Never.enabled(true));
+        assert!(ColorMode::Auto.enabled(true));
+        assert!(!ColorMode::Auto.enabled(false));
+        assert!(ColorMode::Always.enabled(false));
+    }
+
+    #[test]
+    fn colorize_wraps_each_range_and_leaves_the_rest_untouched() {
+        let line = "[ INFO 2024 ]: hello";
+        let colored = colorize(line, log::Level::Info, &[2..6, 7..11]);
+        assert_eq!(
+            colored,
+            "[ \x1b[32mINFO\x1b[0m \x1b[32m2024\x1b[0m ]: hello"
+        );
+    }
+
+    #[test]
+    fn colorize_with_no_ranges_returns_line_unchanged() {
+        let line = "[ INFO ]: hello";
+        assert_eq!(colorize(line, log::Level::Info, &[]), line);
+    }
+}
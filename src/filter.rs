@@ -0,0 +1,83 @@
+//! Per-target level filtering using env_logger/crosvm-style directive strings, e.g.
+//! `"info,mycrate::net=debug,noisy_dep=off"`.
+
+/// An ordered set of per-target level overrides, plus the default level to fall back to.
+#[derive(Debug, Clone)]
+pub(crate) struct TargetFilter {
+    default: log::LevelFilter,
+    rules: Vec<(String, log::LevelFilter)>,
+}
+impl TargetFilter {
+    /// Parses a comma-separated directive string. Each entry is either a bare level, which
+    /// overrides `default`, or a `target=level` pair, which overrides the level for `target` and
+    /// any of its submodules. Unparsable entries are ignored.
+    pub(crate) fn parse(directives: &str, default: log::LevelFilter) -> Self {
+        let mut default = default;
+        let mut rules = Vec::new();
+        for entry in directives
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            match entry.split_once('=') {
+                Some((target, level)) => {
+                    if let Ok(level) = level.trim().parse() {
+                        rules.push((target.trim().to_string(), level));
+                    }
+                }
+                None => {
+                    if let Ok(level) = entry.parse() {
+                        default = level;
+                    }
+                }
+            }
+        }
+        Self { default, rules }
+    }
+
+    /// The level filter in effect for `target`: the override with the longest matching target
+    /// prefix, or [`Self::default`] if none match.
+    pub(crate) fn level_for(&self, target: &str) -> log::LevelFilter {
+        self.rules
+            .iter()
+            .filter(|(prefix, _)| target.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// The most permissive level across the default and every override, suitable for
+    /// [`log::set_max_level`] so the `log` crate doesn't discard records before [`Self::level_for`]
+    /// gets a chance to evaluate them per-target.
+    pub(crate) fn max_level(&self) -> log::LevelFilter {
+        self.rules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, |a, b| a.max(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_prefix_wins() {
+        let filter = TargetFilter::parse(
+            "info,mycrate::net=debug,noisy_dep=off",
+            log::LevelFilter::Warn,
+        );
+        assert_eq!(filter.level_for("mycrate::net"), log::LevelFilter::Debug);
+        assert_eq!(
+            filter.level_for("mycrate::net::tcp"),
+            log::LevelFilter::Debug
+        );
+        assert_eq!(filter.level_for("noisy_dep"), log::LevelFilter::Off);
+        assert_eq!(filter.level_for("mycrate::other"), log::LevelFilter::Info);
+    }
+
+    #[test]
+    fn max_level_is_most_permissive() {
+        let filter = TargetFilter::parse("warn,mycrate::net=trace", log::LevelFilter::Info);
+        assert_eq!(filter.max_level(), log::LevelFilter::Trace);
+    }
+}
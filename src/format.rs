@@ -0,0 +1,107 @@
+//! Parsing of user-supplied log line templates into renderable segments.
+
+/// The default template, reproducing the historical `[ {timestamp} {t} {L} ]: {s}` layout.
+pub(crate) const DEFAULT_TEMPLATE: &str = "[ {timestamp} {t} {L} ]: {s}";
+
+/// A single piece of a parsed log line template.
+///
+/// Produced by [`parse`] and rendered in sequence by `Logger::log`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LogSegment {
+    Literal(String),
+    Timestamp,
+    Level,
+    Target,
+    ModulePath,
+    File,
+    Line,
+    Message,
+}
+
+/// Parses a log line template into a sequence of [`LogSegment`]s.
+///
+/// Recognized markers are `{timestamp}`, `{L}` (level), `{t}` (target), `{module}` (module
+/// path), `{f}` (file), `{line}` (line number), and `{s}` (message). A literal `{` is written
+/// as `{{`. Anything else, including an unrecognized `{...}`, is copied through as literal text.
+pub(crate) fn parse(template: &str) -> Vec<LogSegment> {
+    const MARKERS: &[(&str, LogSegment)] = &[
+        ("{timestamp}", LogSegment::Timestamp),
+        ("{module}", LogSegment::ModulePath),
+        ("{line}", LogSegment::Line),
+        ("{L}", LogSegment::Level),
+        ("{t}", LogSegment::Target),
+        ("{f}", LogSegment::File),
+        ("{s}", LogSegment::Message),
+    ];
+
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut rest = template;
+    while !rest.is_empty() {
+        if let Some(escaped) = rest.strip_prefix("{{") {
+            literal.push('{');
+            rest = escaped;
+            continue;
+        }
+        if let Some((marker, segment)) = MARKERS.iter().find(|(marker, _)| rest.starts_with(marker))
+        {
+            if !literal.is_empty() {
+                segments.push(LogSegment::Literal(std::mem::take(&mut literal)));
+            }
+            segments.push(segment.clone());
+            rest = &rest[marker.len()..];
+            continue;
+        }
+        let mut chars = rest.chars();
+        literal.push(chars.next().expect("rest is non-empty"));
+        rest = chars.as_str();
+    }
+    if !literal.is_empty() {
+        segments.push(LogSegment::Literal(literal));
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_default_template() {
+        assert_eq!(
+            parse(DEFAULT_TEMPLATE),
+            vec![
+                LogSegment::Literal("[ ".to_string()),
+                LogSegment::Timestamp,
+                LogSegment::Literal(" ".to_string()),
+                LogSegment::Target,
+                LogSegment::Literal(" ".to_string()),
+                LogSegment::Level,
+                LogSegment::Literal(" ]: ".to_string()),
+                LogSegment::Message,
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_double_brace() {
+        // Only `{{` is special; a bare `}` has no escape of its own, so both `}` in the trailing
+        // `}}` survive as literal text.
+        assert_eq!(
+            parse("{{{s}}}"),
+            vec![
+                LogSegment::Literal("{".to_string()),
+                LogSegment::Message,
+                LogSegment::Literal("}}".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unrecognized_marker_is_literal() {
+        assert_eq!(
+            parse("{oops}"),
+            vec![LogSegment::Literal("{oops}".to_string())]
+        );
+    }
+}
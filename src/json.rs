@@ -0,0 +1,141 @@
+//! Structured single-line JSON log output, as an alternative to the human-readable templated
+//! format, inspired by Fuchsia's structured log formatter. Implemented by hand to avoid pulling
+//! in a JSON dependency just for this.
+
+use std::fmt::Write as _;
+
+/// A [`std::fmt::Write`] adapter that JSON-escapes everything written through it.
+struct Escaped<'a>(&'a mut String);
+impl std::fmt::Write for Escaped<'_> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        for c in s.chars() {
+            match c {
+                '"' => self.0.push_str("\\\""),
+                '\\' => self.0.push_str("\\\\"),
+                '\n' => self.0.push_str("\\n"),
+                '\r' => self.0.push_str("\\r"),
+                '\t' => self.0.push_str("\\t"),
+                c if (c as u32) < 0x20 => write!(self.0, "\\u{:04x}", c as u32)?,
+                c => self.0.push(c),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_quoted(out: &mut String, value: &str) {
+    out.push('"');
+    Escaped(out)
+        .write_str(value)
+        .expect("Failed to escape JSON string");
+    out.push('"');
+}
+
+/// A `Display` impl in `args` that fails leaves the message cut short where it failed, still
+/// quoted, instead of panicking.
+fn write_quoted_args(out: &mut String, args: std::fmt::Arguments<'_>) {
+    out.push('"');
+    let _ = write!(Escaped(out), "{args}");
+    out.push('"');
+}
+
+fn write_opt_str(out: &mut String, value: Option<&str>) {
+    match value {
+        Some(value) => write_quoted(out, value),
+        None => out.push_str("null"),
+    }
+}
+
+/// Appends one record to `out` as a single-line JSON object with `timestamp`, `level`, `target`,
+/// `module`, `file`, `line`, and `message` fields, followed by a newline.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn render(
+    out: &mut String,
+    timestamp: &str,
+    level: log::Level,
+    target: &str,
+    module: Option<&str>,
+    file: Option<&str>,
+    line: Option<u32>,
+    message: std::fmt::Arguments<'_>,
+) {
+    out.push('{');
+    out.push_str("\"timestamp\":");
+    write_quoted(out, timestamp);
+    out.push_str(",\"level\":");
+    write_quoted(out, level.as_str());
+    out.push_str(",\"target\":");
+    write_quoted(out, target);
+    out.push_str(",\"module\":");
+    write_opt_str(out, module);
+    out.push_str(",\"file\":");
+    write_opt_str(out, file);
+    out.push_str(",\"line\":");
+    match line {
+        Some(n) => write!(out, "{n}").expect("Failed to format line number"),
+        None => out.push_str("null"),
+    }
+    out.push_str(",\"message\":");
+    write_quoted_args(out, message);
+    out.push('}');
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_all_fields() {
+        let mut out = String::new();
+        render(
+            &mut out,
+            "2024-01-01T00:00:00Z",
+            log::Level::Info,
+            "mycrate::net",
+            Some("mycrate::net"),
+            Some("src/net.rs"),
+            Some(42),
+            format_args!("hello"),
+        );
+        assert_eq!(
+            out,
+            "{\"timestamp\":\"2024-01-01T00:00:00Z\",\"level\":\"INFO\",\"target\":\"mycrate::net\",\"module\":\"mycrate::net\",\"file\":\"src/net.rs\",\"line\":42,\"message\":\"hello\"}\n"
+        );
+    }
+
+    #[test]
+    fn renders_missing_fields_as_null() {
+        let mut out = String::new();
+        render(
+            &mut out,
+            "2024-01-01T00:00:00Z",
+            log::Level::Warn,
+            "mycrate",
+            None,
+            None,
+            None,
+            format_args!("hi"),
+        );
+        assert_eq!(
+            out,
+            "{\"timestamp\":\"2024-01-01T00:00:00Z\",\"level\":\"WARN\",\"target\":\"mycrate\",\"module\":null,\"file\":null,\"line\":null,\"message\":\"hi\"}\n"
+        );
+    }
+
+    #[test]
+    fn escapes_special_characters_in_the_message() {
+        let mut out = String::new();
+        render(
+            &mut out,
+            "2024-01-01T00:00:00Z",
+            log::Level::Error,
+            "mycrate",
+            None,
+            None,
+            None,
+            format_args!("line one\n\"quoted\"\t\\"),
+        );
+        assert!(out.contains("\"message\":\"line one\\n\\\"quoted\\\"\\t\\\\\""));
+    }
+}
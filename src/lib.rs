@@ -1,27 +1,59 @@
 //! A simple logger that writes to stdout and optionally also to a log file.
 //!
-//! The logger is initialized by calling [`init`], and after that one can use log's macros. Each log will be in the format [ A B C ]: D, where A is the UTC time and date in the RFC 3339 format, B is log's target, C is the log level, and D is the actual message.
+//! The logger is initialized by calling [`init`] for the defaults, or [`Builder`] to configure a
+//! logfile, rotation, a custom format template, or per-target level filtering. After that one
+//! can use log's macros. By default each log will be in the format [ A B C ]: D, where A is the
+//! UTC time and date in the RFC 3339 format, B is log's target, C is the log level, and D is the
+//! actual message.
 //!
-//! # Panics
+//! # Error handling
 //!
-//! The logger can panic during logging if writing to stdout of the log file returns an error, the time fails to format, or if internal synchonization becomes poisoned.
+//! Logging never panics the host application: a write error, a time-formatting failure, or a
+//! poisoned internal lock is reported to an error handler instead, installed via
+//! [`Builder::error_handler`] and defaulting to a one-line diagnostic on stderr. Reentrant
+//! logging (e.g. a logged value whose `Display` impl itself logs) is also handled: it falls back
+//! to a freshly allocated buffer instead of panicking on an already-borrowed thread-local.
 //!
 //! # Examples
 //!
 //! ```
-//! hydrolox_log::init(log::LevelFilter::Info, false).unwrap();
+//! hydrolox_log::init(log::LevelFilter::Info).unwrap();
 //! log::info!("Logging works!");
 //! ```
 
+mod color;
+mod filter;
+mod format;
+mod json;
+mod rotation;
+#[cfg(all(unix, feature = "syslog"))]
+mod syslog;
+mod writer;
+
 use std::{
-    fmt::Display,
+    cell::RefCell,
+    fmt::{Display, Write as _},
     fs::File,
-    io::{stdout, BufWriter, Write},
-    sync::{Mutex, OnceLock},
+    io::{stdout, BufWriter, IsTerminal, Write},
+    ops::Range,
+    sync::{Arc, OnceLock},
 };
 
-use log::Log;
+pub use color::ColorMode;
+use filter::TargetFilter;
+use format::LogSegment;
+pub use rotation::RotationPolicy;
+use rotation::RotationState;
+#[cfg(all(unix, feature = "syslog"))]
+pub use syslog::Facility;
+#[cfg(all(unix, feature = "syslog"))]
+use syslog::SyslogSink;
 use time::{format_description::BorrowedFormatItem, macros::format_description, OffsetDateTime};
+pub use writer::WriteMode;
+use writer::{FileSink, LogFile};
+
+/// The default log line template, reproducing the historical `[ {timestamp} {t} {L} ]: {s}` layout.
+pub const DEFAULT_FORMAT: &str = format::DEFAULT_TEMPLATE;
 
 const FILENAME_FORMAT: &[BorrowedFormatItem<'_>] =
     format_description!("[year]-[month]-[day]T[hour repr:24]_[minute]_[second]");
@@ -30,18 +62,16 @@ const ENTRY_FORMAT: &[BorrowedFormatItem<'_>] =
     format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:4]");
 
 fn now_formatted(format: &[BorrowedFormatItem<'_>]) -> Result<String, time::error::Format> {
-    OffsetDateTime::now_utc()
-        .format(format)
-        .map(|mut s| {
-            unsafe {
-                s.as_bytes_mut().iter_mut().for_each(|b| {
-                    if *b == b':' {
-                        *b = b'_'
-                    }
-                })
-            };
-            s
-        })
+    OffsetDateTime::now_utc().format(format).map(|mut s| {
+        unsafe {
+            s.as_bytes_mut().iter_mut().for_each(|b| {
+                if *b == b':' {
+                    *b = b'_'
+                }
+            })
+        };
+        s
+    })
 }
 fn now_filename() -> Result<String, time::error::Format> {
     now_formatted(FILENAME_FORMAT)
@@ -49,6 +79,9 @@ fn now_filename() -> Result<String, time::error::Format> {
 fn now_entry() -> Result<String, time::error::Format> {
     now_formatted(ENTRY_FORMAT)
 }
+fn now_rfc3339() -> Result<String, time::error::Format> {
+    OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339)
+}
 
 #[derive(Debug)]
 pub enum LoggerInitError {
@@ -57,6 +90,9 @@ pub enum LoggerInitError {
     TimeFormatErr(time::error::Format),
     CreateFileErr(std::io::Error),
     SetLoggerErr(log::SetLoggerError),
+    RotationErr(std::io::Error),
+    #[cfg(all(unix, feature = "syslog"))]
+    SyslogOpenErr,
 }
 impl Display for LoggerInitError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -66,6 +102,9 @@ impl Display for LoggerInitError {
             Self::TimeFormatErr(err) => write!(f, "Failed to format current time: {err}"),
             Self::CreateFileErr(err) => write!(f, "Failed to create logfile: {err}"),
             Self::SetLoggerErr(err) => write!(f, "Failed to set logger: {err}"),
+            Self::RotationErr(err) => write!(f, "Failed to rotate logfile: {err}"),
+            #[cfg(all(unix, feature = "syslog"))]
+            Self::SyslogOpenErr => write!(f, "Syslog identity contained a nul byte"),
         }
     }
 }
@@ -77,6 +116,9 @@ impl std::error::Error for LoggerInitError {
             Self::TimeFormatErr(err) => Some(err),
             Self::CreateFileErr(err) => Some(err),
             Self::SetLoggerErr(err) => Some(err),
+            Self::RotationErr(err) => Some(err),
+            #[cfg(all(unix, feature = "syslog"))]
+            Self::SyslogOpenErr => None,
         }
     }
 }
@@ -91,54 +133,196 @@ impl From<log::SetLoggerError> for LoggerInitError {
     }
 }
 
+/// A sink for runtime logging failures (a write error, a time-formatting error, a poisoned
+/// lock) that can't be allowed to panic the host application. Installed via
+/// [`Builder::error_handler`]; defaults to a one-line diagnostic on stderr.
+pub(crate) type ErrorHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// The default [`ErrorHandler`]: writes a one-line diagnostic to stderr.
+fn default_error_handler(message: &str) {
+    eprintln!("hydrolox_log: {message}");
+}
+
+thread_local! {
+    /// Reused across calls to [`Logger::log`] on a given thread to avoid reallocating per record.
+    static LINE_BUFFER: RefCell<String> = const { RefCell::new(String::new()) };
+    /// Reused across calls to [`Logger::log`] when JSON output is in play for either sink.
+    static JSON_BUFFER: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// Runs `f` against the thread-local `buffer`, falling back to a freshly allocated `String` if
+/// it's already borrowed. That only happens on reentrant logging — e.g. a logged value whose
+/// `Display` impl itself logs — and the fallback trades away buffer reuse for that one call
+/// rather than panicking on a double `borrow_mut`.
+fn with_buffer<R>(
+    buffer: &'static std::thread::LocalKey<RefCell<String>>,
+    f: impl FnOnce(&mut String) -> R,
+) -> R {
+    buffer.with(|cell| match cell.try_borrow_mut() {
+        Ok(mut owned) => f(&mut owned),
+        Err(_) => f(&mut String::new()),
+    })
+}
+
 struct Logger {
-    logfile: Mutex<Option<BufWriter<File>>>,
+    logfile: Option<FileSink>,
+    segments: Vec<LogSegment>,
+    filter: TargetFilter,
+    color: ColorMode,
+    json_stdout: bool,
+    json_file: bool,
+    #[cfg(all(unix, feature = "syslog"))]
+    syslog: Option<SyslogSink>,
+    on_error: ErrorHandler,
 }
 impl Logger {
-    fn new(use_logfile: bool) -> Result<Self, LoggerInitError> {
-        let logfile = Mutex::new(if use_logfile {
-            let mut prefix =
-                std::env::current_exe().map_err(|e| LoggerInitError::ExePathGetErr(e))?;
-            prefix.pop();
-            Some(BufWriter::new(
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        use_logfile: bool,
+        write_mode: WriteMode,
+        format: &str,
+        rotation: Option<RotationPolicy>,
+        filter: TargetFilter,
+        color: ColorMode,
+        json_stdout: bool,
+        json_file: bool,
+        #[cfg(all(unix, feature = "syslog"))] syslog: Option<(String, Facility)>,
+        on_error: ErrorHandler,
+    ) -> Result<Self, LoggerInitError> {
+        let logfile = if use_logfile {
+            let mut dir = std::env::current_exe().map_err(|e| LoggerInitError::ExePathGetErr(e))?;
+            dir.pop();
+            let writer = BufWriter::new(
                 File::create(format!(
                     "{}/log_{}.txt",
-                    prefix.to_str().ok_or(LoggerInitError::NonUTF8Path)?,
+                    dir.to_str().ok_or(LoggerInitError::NonUTF8Path)?,
                     now_filename()?
                 ))
                 .map_err(|e| LoggerInitError::CreateFileErr(e))?,
+            );
+            let rotation = rotation
+                .map(|policy| RotationState::new(policy, dir, OffsetDateTime::now_utc().date()));
+            Some(FileSink::new(
+                LogFile::new(writer, rotation, on_error.clone()),
+                write_mode,
             ))
         } else {
             None
-        });
-        Ok(Self { logfile })
+        };
+        #[cfg(all(unix, feature = "syslog"))]
+        let syslog = syslog
+            .map(|(identity, facility)| SyslogSink::open(&identity, facility))
+            .transpose()?;
+        Ok(Self {
+            logfile,
+            segments: format::parse(format),
+            filter,
+            color,
+            json_stdout,
+            json_file,
+            #[cfg(all(unix, feature = "syslog"))]
+            syslog,
+            on_error,
+        })
+    }
+
+    /// Routes a runtime logging failure to the installed [`ErrorHandler`] instead of panicking.
+    fn report_error(&self, message: impl Display) {
+        (self.on_error)(&message.to_string());
     }
 }
 impl log::Log for Logger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= log::max_level()
+        metadata.level() <= self.filter.level_for(metadata.target())
     }
     fn log(&self, record: &log::Record) {
-        if self.enabled(record.metadata()) {
-            let output = format!(
-                "[ {} {} {} ]: {}\n",
-                now_entry().expect("Failed to format current time"),
-                record.target(),
-                record.level(),
-                record.args()
-            );
-            stdout()
-                .write_all(output.as_bytes())
-                .expect("Failed to log to stdout");
-            if let Some(file) = self.logfile.lock().unwrap().as_mut() {
-                file.write_all(output.as_bytes())
-                    .expect("Failed to log to file");
-            }
+        if !self.enabled(record.metadata()) {
+            return;
         }
+        with_buffer(&LINE_BUFFER, |line| {
+            line.clear();
+            let mut color_ranges: Vec<Range<usize>> = Vec::new();
+            for segment in &self.segments {
+                let start = line.len();
+                match segment {
+                    LogSegment::Literal(text) => line.push_str(text),
+                    LogSegment::Timestamp => match now_entry() {
+                        Ok(timestamp) => line.push_str(&timestamp),
+                        Err(err) => {
+                            self.report_error(format_args!("failed to format timestamp: {err}"))
+                        }
+                    },
+                    LogSegment::Level => {
+                        write!(line, "{}", record.level()).expect("Failed to format level")
+                    }
+                    LogSegment::Target => line.push_str(record.target()),
+                    LogSegment::ModulePath => line.push_str(record.module_path().unwrap_or("")),
+                    LogSegment::File => line.push_str(record.file().unwrap_or("")),
+                    LogSegment::Line => {
+                        if let Some(n) = record.line() {
+                            write!(line, "{n}").expect("Failed to format line number");
+                        }
+                    }
+                    LogSegment::Message => {
+                        if let Err(err) = write!(line, "{}", record.args()) {
+                            self.report_error(format_args!("failed to format message: {err}"));
+                        }
+                    }
+                }
+                if matches!(segment, LogSegment::Level | LogSegment::Timestamp) {
+                    color_ranges.push(start..line.len());
+                }
+            }
+            #[cfg(all(unix, feature = "syslog"))]
+            if let Some(syslog) = self.syslog.as_ref() {
+                if let Err(err) = syslog.log(record.level(), line) {
+                    self.report_error(format_args!("failed to send to syslog: {err}"));
+                }
+            }
+            line.push('\n');
+
+            with_buffer(&JSON_BUFFER, |json_line| {
+                if self.json_stdout || self.json_file {
+                    json_line.clear();
+                    let timestamp = now_rfc3339().unwrap_or_else(|err| {
+                        self.report_error(format_args!("failed to format timestamp: {err}"));
+                        String::from("unknown")
+                    });
+                    json::render(
+                        json_line,
+                        &timestamp,
+                        record.level(),
+                        record.target(),
+                        record.module_path(),
+                        record.file(),
+                        record.line(),
+                        *record.args(),
+                    );
+                }
+
+                if self.json_stdout {
+                    if let Err(err) = stdout().write_all(json_line.as_bytes()) {
+                        self.report_error(format_args!("failed to write to stdout: {err}"));
+                    }
+                } else if self.color.enabled(stdout().is_terminal()) {
+                    let colored = color::colorize(line, record.level(), &color_ranges);
+                    if let Err(err) = stdout().write_all(colored.as_bytes()) {
+                        self.report_error(format_args!("failed to write to stdout: {err}"));
+                    }
+                } else if let Err(err) = stdout().write_all(line.as_bytes()) {
+                    self.report_error(format_args!("failed to write to stdout: {err}"));
+                }
+
+                if let Some(logfile) = self.logfile.as_ref() {
+                    let file_line = if self.json_file { &json_line } else { &line };
+                    logfile.write(file_line.as_bytes());
+                }
+            });
+        });
     }
     fn flush(&self) {
-        if let Some(file) = self.logfile.lock().unwrap().as_mut() {
-            file.flush().expect("Failed to flush logfile");
+        if let Some(logfile) = self.logfile.as_ref() {
+            logfile.flush();
         }
     }
 }
@@ -149,43 +333,225 @@ pub struct LogState {}
 impl Drop for LogState {
     fn drop(&mut self) {
         if let Some(logger) = LOGGER.get() {
-            logger.flush();
+            if let Some(logfile) = logger.logfile.as_ref() {
+                logfile.shutdown();
+            }
         }
     }
 }
 
-/// Initializes the logger.
-///
-/// If `use_logfile` is true, then the logger will also output log messages to a logfile located in the same path as the current executable. The file will be called log_X.txt, where X is the UTC time and date the logger was initialized in the RFC 3339 format. If writing to the logfile is enabled, then the function will return Some(LogState). This state should be dropped after all logging is complete to flush the logile.
+/// Configures and installs the global logger, following the same builder shape as flexi_logger.
 ///
-/// # Errors
-///
-/// The function will return an error if the logger is already set. Additionally, if `use_logfile` is true, the function will return an error if:
-///  - Getting the executable's current path returns an error
-///  - Said path contains non-UTF8 characters
-///  - Attempting to create the logfile returns an error
-#[must_use]
-pub fn init(
+/// Created with [`Builder::new`], which sets the default level to fall back to when nothing more
+/// specific applies, configured with the fluent setters below, and finished with [`Builder::init`].
+pub struct Builder {
     max_level: log::LevelFilter,
     use_logfile: bool,
-) -> Result<Option<LogState>, LoggerInitError> {
-    _ = LOGGER.set(Logger::new(use_logfile)?);
-    log::set_logger(LOGGER.get().unwrap())?;
-    log::set_max_level(max_level);
-    if use_logfile {
-        Ok(Some(LogState {}))
-    } else {
-        Ok(None)
+    write_mode: WriteMode,
+    format: String,
+    rotation: Option<RotationPolicy>,
+    filter: String,
+    filter_env_var: Option<String>,
+    color: ColorMode,
+    json_stdout: bool,
+    json_file: bool,
+    #[cfg(all(unix, feature = "syslog"))]
+    syslog: Option<(String, Facility)>,
+    on_error: ErrorHandler,
+}
+impl Builder {
+    /// Creates a builder with `max_level` as the default level and every other option at its
+    /// default: no logfile, [`WriteMode::Direct`] writes, [`DEFAULT_FORMAT`], no rotation, no
+    /// per-target filtering, no color, human-readable output on both sinks, and an error handler
+    /// that writes a one-line diagnostic to stderr.
+    pub fn new(max_level: log::LevelFilter) -> Self {
+        Self {
+            max_level,
+            use_logfile: false,
+            write_mode: WriteMode::Direct,
+            format: DEFAULT_FORMAT.to_string(),
+            rotation: None,
+            filter: String::new(),
+            filter_env_var: None,
+            color: ColorMode::Never,
+            json_stdout: false,
+            json_file: false,
+            #[cfg(all(unix, feature = "syslog"))]
+            syslog: None,
+            on_error: Arc::new(default_error_handler),
+        }
+    }
+
+    /// If `use_logfile` is true, also writes log lines to a logfile in the same directory as the
+    /// current executable, named log_X.txt, where X is the UTC time and date the logger was
+    /// initialized in the RFC 3339 format. If enabled, [`Builder::init`] returns `Some(LogState)`,
+    /// which should be dropped after all logging is complete to flush the logfile.
+    pub fn use_logfile(mut self, use_logfile: bool) -> Self {
+        self.use_logfile = use_logfile;
+        self
+    }
+
+    /// Chooses how writes to the logfile reach disk: synchronously on the logging thread
+    /// (the default), or handed off to a background thread per [`WriteMode::BufferedAsync`] so
+    /// hot logging paths don't pay file I/O latency. Only takes effect when
+    /// [`Builder::use_logfile`] is enabled.
+    pub fn write_mode(mut self, mode: WriteMode) -> Self {
+        self.write_mode = mode;
+        self
+    }
+
+    /// Sets the log line template, recognizing the markers `{timestamp}`, `{L}` (level), `{t}`
+    /// (target), `{module}` (module path), `{f}` (file), `{line}` (line number), and `{s}`
+    /// (message); a literal `{` is written as `{{`. It is parsed once, in [`Builder::init`], into
+    /// a sequence of segments that are then rendered for every record.
+    pub fn format(mut self, format: impl Into<String>) -> Self {
+        self.format = format.into();
+        self
+    }
+
+    /// Rotates the logfile per `policy` instead of letting it grow for the life of the process.
+    /// Only takes effect when [`Builder::use_logfile`] is enabled.
+    pub fn rotation(mut self, policy: RotationPolicy) -> Self {
+        self.rotation = Some(policy);
+        self
+    }
+
+    /// Sets per-target level overrides using an env_logger/crosvm-style directive string, e.g.
+    /// `"info,mycrate::net=debug,noisy_dep=off"`: a bare level overrides the default passed to
+    /// [`Builder::new`], and a `target=level` pair overrides the level for that target and its
+    /// submodules. The most specific (longest prefix) matching rule wins.
+    pub fn filter(mut self, directives: impl Into<String>) -> Self {
+        self.filter = directives.into();
+        self
+    }
+
+    /// Names an environment variable whose value, if set, is used as the filter directive string
+    /// instead of the one passed to [`Builder::filter`], letting operators change verbosity
+    /// without recompiling.
+    pub fn filter_env_var(mut self, name: impl Into<String>) -> Self {
+        self.filter_env_var = Some(name.into());
+        self
+    }
+
+    /// Colorizes the level (and timestamp) in stdout output per [`ColorMode`], by level: red for
+    /// [`log::Level::Error`], yellow for [`log::Level::Warn`], green for [`log::Level::Info`], and
+    /// dimmed for [`log::Level::Debug`]/[`log::Level::Trace`]. The logfile and syslog sinks always
+    /// receive the uncolored line.
+    pub fn color(mut self, mode: ColorMode) -> Self {
+        self.color = mode;
+        self
+    }
+
+    /// Emits structured single-line JSON objects (`timestamp`, `level`, `target`, `module`,
+    /// `file`, `line`, `message`) on stdout instead of the human-readable format template.
+    pub fn json_stdout(mut self, json: bool) -> Self {
+        self.json_stdout = json;
+        self
+    }
+
+    /// Emits structured single-line JSON objects to the logfile instead of the human-readable
+    /// format template. Only takes effect when [`Builder::use_logfile`] is enabled. Independent
+    /// of [`Builder::json_stdout`], so one sink can stay human-readable while the other is
+    /// machine-parseable.
+    pub fn json_file(mut self, json: bool) -> Self {
+        self.json_file = json;
+        self
+    }
+
+    /// Also sends log lines to syslog, opened under `identity` and `facility` via `openlog(3)`.
+    /// Only available on Unix with the `syslog` feature enabled.
+    #[cfg(all(unix, feature = "syslog"))]
+    pub fn syslog(mut self, identity: impl Into<String>, facility: Facility) -> Self {
+        self.syslog = Some((identity.into(), facility));
+        self
+    }
+
+    /// Installs `handler` to receive a one-line description of any runtime logging failure (a
+    /// write error, a time-formatting error, a poisoned lock) instead of the logger panicking.
+    /// Replaces the default, which writes the description to stderr.
+    pub fn error_handler(mut self, handler: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.on_error = Arc::new(handler);
+        self
+    }
+
+    /// Builds and installs the logger.
+    ///
+    /// # Errors
+    ///
+    /// The function will return an error if the logger is already set. Additionally, if logfile
+    /// use is enabled, the function will return an error if:
+    ///  - Getting the executable's current path returns an error
+    ///  - Said path contains non-UTF8 characters
+    ///  - Attempting to create the logfile returns an error
+    #[must_use = "if logfile use is enabled, dropping the returned LogState immediately flushes \
+                  and closes it instead of keeping it open for the life of the program"]
+    pub fn init(self) -> Result<Option<LogState>, LoggerInitError> {
+        let directives = self
+            .filter_env_var
+            .as_deref()
+            .and_then(|name| std::env::var(name).ok())
+            .unwrap_or(self.filter);
+        let filter = TargetFilter::parse(&directives, self.max_level);
+        let max_level = filter.max_level();
+        _ = LOGGER.set(Logger::new(
+            self.use_logfile,
+            self.write_mode,
+            &self.format,
+            self.rotation,
+            filter,
+            self.color,
+            self.json_stdout,
+            self.json_file,
+            #[cfg(all(unix, feature = "syslog"))]
+            self.syslog,
+            self.on_error,
+        )?);
+        log::set_logger(LOGGER.get().unwrap())?;
+        log::set_max_level(max_level);
+        if self.use_logfile {
+            Ok(Some(LogState {}))
+        } else {
+            Ok(None)
+        }
     }
 }
 
+/// Initializes the logger with `max_level` as the only configuration: stdout output only, the
+/// default format, and no rotation or per-target filtering. See [`Builder`] for everything else.
+///
+/// # Errors
+///
+/// The function will return an error if the logger is already set.
+#[must_use = "if logfile use is enabled, dropping the returned LogState immediately flushes \
+              and closes it instead of keeping it open for the life of the program"]
+pub fn init(max_level: log::LevelFilter) -> Result<Option<LogState>, LoggerInitError> {
+    Builder::new(max_level).init()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn with_buffer_falls_back_instead_of_panicking_when_reentered() {
+        // Models a logged value whose `Display` impl itself logs: the inner call re-enters
+        // `with_buffer` on the same thread-local while the outer borrow is still held.
+        with_buffer(&LINE_BUFFER, |outer| {
+            outer.push_str("outer");
+            with_buffer(&LINE_BUFFER, |inner| {
+                inner.push_str("inner");
+                assert_eq!(inner, "inner");
+            });
+            assert_eq!(outer, "outer");
+        });
+    }
+
     #[test]
     fn open_logfile() {
-        let _log_state = init(log::LevelFilter::Debug, true).unwrap();
+        let _log_state = Builder::new(log::LevelFilter::Debug)
+            .use_logfile(true)
+            .init()
+            .unwrap();
         log::debug!("Testing")
     }
 }
@@ -0,0 +1,113 @@
+//! An optional syslog sink for Unix deployments, using libc's `openlog`/`syslog`/`closelog`
+//! (POSIX syslog, local daemon only). Gated behind `#[cfg(unix)]` and the `syslog` feature.
+
+use std::ffi::CString;
+
+use crate::LoggerInitError;
+
+/// Format string passed to `syslog(3)`, so an arbitrary message is never interpreted as one.
+const MESSAGE_FORMAT: &[u8] = b"%s\0";
+
+/// Syslog facility to register under, matching libc's `LOG_*` facility codes.
+#[derive(Debug, Clone, Copy)]
+pub enum Facility {
+    Daemon,
+    User,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+impl Facility {
+    fn as_raw(self) -> libc::c_int {
+        match self {
+            Self::Daemon => libc::LOG_DAEMON,
+            Self::User => libc::LOG_USER,
+            Self::Local0 => libc::LOG_LOCAL0,
+            Self::Local1 => libc::LOG_LOCAL1,
+            Self::Local2 => libc::LOG_LOCAL2,
+            Self::Local3 => libc::LOG_LOCAL3,
+            Self::Local4 => libc::LOG_LOCAL4,
+            Self::Local5 => libc::LOG_LOCAL5,
+            Self::Local6 => libc::LOG_LOCAL6,
+            Self::Local7 => libc::LOG_LOCAL7,
+        }
+    }
+}
+
+/// Holds the syslog connection opened by `openlog`, closing it with `closelog` on drop.
+pub(crate) struct SyslogSink {
+    /// `openlog` may retain this pointer for as long as the connection is open, so it must
+    /// outlive every `syslog` call made through this sink.
+    _ident: CString,
+}
+impl SyslogSink {
+    pub(crate) fn open(identity: &str, facility: Facility) -> Result<Self, LoggerInitError> {
+        let ident = CString::new(identity).map_err(|_| LoggerInitError::SyslogOpenErr)?;
+        unsafe {
+            libc::openlog(ident.as_ptr(), libc::LOG_PID, facility.as_raw());
+        }
+        Ok(Self { _ident: ident })
+    }
+
+    /// Hands the already-rendered `message` to `syslog(3)` at the priority matching `level`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `message` contains a nul byte, in which case nothing is sent.
+    pub(crate) fn log(&self, level: log::Level, message: &str) -> Result<(), std::ffi::NulError> {
+        let priority = priority_for(level);
+        let message = CString::new(message)?;
+        unsafe {
+            libc::syslog(priority, MESSAGE_FORMAT.as_ptr().cast(), message.as_ptr());
+        }
+        Ok(())
+    }
+}
+impl Drop for SyslogSink {
+    fn drop(&mut self) {
+        unsafe { libc::closelog() };
+    }
+}
+
+/// Maps a log level to the `syslog(3)` priority it's sent at.
+fn priority_for(level: log::Level) -> libc::c_int {
+    match level {
+        log::Level::Error => libc::LOG_ERR,
+        log::Level::Warn => libc::LOG_WARNING,
+        log::Level::Info => libc::LOG_INFO,
+        log::Level::Debug | log::Level::Trace => libc::LOG_DEBUG,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn facility_maps_to_libc_codes() {
+        assert_eq!(Facility::Daemon.as_raw(), libc::LOG_DAEMON);
+        assert_eq!(Facility::User.as_raw(), libc::LOG_USER);
+        assert_eq!(Facility::Local0.as_raw(), libc::LOG_LOCAL0);
+        assert_eq!(Facility::Local1.as_raw(), libc::LOG_LOCAL1);
+        assert_eq!(Facility::Local2.as_raw(), libc::LOG_LOCAL2);
+        assert_eq!(Facility::Local3.as_raw(), libc::LOG_LOCAL3);
+        assert_eq!(Facility::Local4.as_raw(), libc::LOG_LOCAL4);
+        assert_eq!(Facility::Local5.as_raw(), libc::LOG_LOCAL5);
+        assert_eq!(Facility::Local6.as_raw(), libc::LOG_LOCAL6);
+        assert_eq!(Facility::Local7.as_raw(), libc::LOG_LOCAL7);
+    }
+
+    #[test]
+    fn level_maps_to_syslog_priority() {
+        assert_eq!(priority_for(log::Level::Error), libc::LOG_ERR);
+        assert_eq!(priority_for(log::Level::Warn), libc::LOG_WARNING);
+        assert_eq!(priority_for(log::Level::Info), libc::LOG_INFO);
+        assert_eq!(priority_for(log::Level::Debug), libc::LOG_DEBUG);
+        assert_eq!(priority_for(log::Level::Trace), libc::LOG_DEBUG);
+    }
+}
@@ -0,0 +1,269 @@
+//! The logfile sink: either written to directly on the logging thread, or handed off to a
+//! dedicated background thread for non-blocking, periodically-flushed writes, modeled on
+//! flexi_logger's write modes.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use time::OffsetDateTime;
+
+use crate::{rotation::RotationState, ErrorHandler};
+
+/// Controls how writes to the logfile reach disk.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum WriteMode {
+    /// Write synchronously on the calling thread through a `BufWriter`, which reaches disk when
+    /// its buffer fills, on [`log::Log::flush`], and when the `LogState` is dropped. This is the
+    /// historical behavior.
+    #[default]
+    Direct,
+    /// Hand formatted lines to a dedicated background thread, which writes them and flushes
+    /// every `flush_interval`, so hot logging paths don't pay file I/O latency.
+    BufferedAsync { flush_interval: Duration },
+}
+
+/// An open logfile and its rotation bookkeeping.
+pub(crate) struct LogFile {
+    writer: BufWriter<File>,
+    rotation: Option<RotationState>,
+    on_error: ErrorHandler,
+}
+impl LogFile {
+    pub(crate) fn new(
+        writer: BufWriter<File>,
+        rotation: Option<RotationState>,
+        on_error: ErrorHandler,
+    ) -> Self {
+        Self {
+            writer,
+            rotation,
+            on_error,
+        }
+    }
+
+    fn report_error(&self, message: impl std::fmt::Display) {
+        (self.on_error)(&message.to_string());
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        if let Err(err) = self.writer.write_all(bytes) {
+            self.report_error(format_args!("failed to write to logfile: {err}"));
+            return;
+        }
+        let Some(rotation) = self.rotation.as_mut() else {
+            return;
+        };
+        rotation.record_write(bytes.len() as u64);
+        let today = OffsetDateTime::now_utc().date();
+        if !rotation.should_rotate(today) {
+            return;
+        }
+        if let Err(err) = self.writer.flush() {
+            self.report_error(format_args!(
+                "failed to flush logfile before rotating: {err}"
+            ));
+            return;
+        }
+        match rotation.rotate(today, &self.on_error) {
+            Ok(writer) => self.writer = writer,
+            Err(err) => self.report_error(format_args!("failed to rotate logfile: {err}")),
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Err(err) = self.writer.flush() {
+            self.report_error(format_args!("failed to flush logfile: {err}"));
+        }
+    }
+}
+
+enum WriterMsg {
+    Write(Vec<u8>),
+    Flush(Sender<()>),
+    Shutdown,
+}
+
+/// Owns a [`LogFile`] on a dedicated thread, receiving writes over an `mpsc` channel so the
+/// logging thread never blocks on file I/O.
+pub(crate) struct BackgroundWriter {
+    sender: Sender<WriterMsg>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+impl BackgroundWriter {
+    fn spawn(mut logfile: LogFile, flush_interval: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let mut next_flush = Instant::now() + flush_interval;
+            loop {
+                let timeout = next_flush.saturating_duration_since(Instant::now());
+                match receiver.recv_timeout(timeout) {
+                    Ok(WriterMsg::Write(bytes)) => logfile.write(&bytes),
+                    Ok(WriterMsg::Flush(ack)) => {
+                        logfile.flush();
+                        let _ = ack.send(());
+                    }
+                    Ok(WriterMsg::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                        logfile.flush();
+                        break;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                }
+                if Instant::now() >= next_flush {
+                    logfile.flush();
+                    next_flush = Instant::now() + flush_interval;
+                }
+            }
+        });
+        Self {
+            sender,
+            handle: Mutex::new(Some(handle)),
+        }
+    }
+
+    fn write(&self, bytes: Vec<u8>) {
+        let _ = self.sender.send(WriterMsg::Write(bytes));
+    }
+
+    fn flush(&self) {
+        let (ack, ack_rx) = mpsc::channel();
+        if self.sender.send(WriterMsg::Flush(ack)).is_ok() {
+            let _ = ack_rx.recv();
+        }
+    }
+
+    /// Signals the worker to flush and exit, then joins it so no buffered records are lost.
+    fn shutdown(&self) {
+        let _ = self.sender.send(WriterMsg::Shutdown);
+        let mut handle = self
+            .handle
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Some(handle) = handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+impl Drop for BackgroundWriter {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Locks `logfile`, recovering the inner value instead of panicking if a prior writer panicked
+/// while holding the lock; a logger must not go on to panic the host application itself. The
+/// recovery is reported to the error handler once and the poison flag is cleared.
+fn lock_logfile(logfile: &Mutex<LogFile>) -> std::sync::MutexGuard<'_, LogFile> {
+    logfile.lock().unwrap_or_else(|poisoned| {
+        logfile.clear_poison();
+        let guard = poisoned.into_inner();
+        guard.report_error("logfile lock was poisoned by a panicking writer; recovered it");
+        guard
+    })
+}
+
+/// The logfile sink, in either write mode.
+pub(crate) enum FileSink {
+    Direct(Mutex<LogFile>),
+    Async(BackgroundWriter),
+}
+impl FileSink {
+    pub(crate) fn new(logfile: LogFile, mode: WriteMode) -> Self {
+        match mode {
+            WriteMode::Direct => Self::Direct(Mutex::new(logfile)),
+            WriteMode::BufferedAsync { flush_interval } => {
+                Self::Async(BackgroundWriter::spawn(logfile, flush_interval))
+            }
+        }
+    }
+
+    pub(crate) fn write(&self, bytes: &[u8]) {
+        match self {
+            Self::Direct(logfile) => lock_logfile(logfile).write(bytes),
+            Self::Async(background) => background.write(bytes.to_vec()),
+        }
+    }
+
+    pub(crate) fn flush(&self) {
+        match self {
+            Self::Direct(logfile) => lock_logfile(logfile).flush(),
+            Self::Async(background) => background.flush(),
+        }
+    }
+
+    /// Flushes and, for the async mode, stops the background thread and waits for it to finish
+    /// writing everything already handed to it.
+    pub(crate) fn shutdown(&self) {
+        match self {
+            Self::Direct(logfile) => lock_logfile(logfile).flush(),
+            Self::Async(background) => background.shutdown(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    static TEST_FILE_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    /// A fresh path under the system temp dir, unique to this test process and call.
+    fn temp_file() -> std::path::PathBuf {
+        let n = TEST_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("hydrolox_log_writer_test_{}_{n}.txt", std::process::id()))
+    }
+
+    fn panicking_error_handler() -> ErrorHandler {
+        std::sync::Arc::new(|msg: &str| panic!("unexpected error: {msg}"))
+    }
+
+    #[test]
+    fn background_writer_writes_land_on_disk_after_shutdown() {
+        let path = temp_file();
+        let writer = BufWriter::new(File::create(&path).unwrap());
+        let logfile = LogFile::new(writer, None, panicking_error_handler());
+        // Longer than the test, so only the explicit shutdown flush is exercised, not the
+        // periodic one - this is what regressed in c529851's "writes lost on shutdown" bug.
+        let sink = FileSink::new(
+            logfile,
+            WriteMode::BufferedAsync {
+                flush_interval: Duration::from_secs(60),
+            },
+        );
+        sink.write(b"hello\n");
+        sink.write(b"world\n");
+        sink.shutdown();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(contents, "hello\nworld\n");
+    }
+
+    #[test]
+    fn background_writer_flush_waits_for_pending_writes() {
+        let path = temp_file();
+        let writer = BufWriter::new(File::create(&path).unwrap());
+        let logfile = LogFile::new(writer, None, panicking_error_handler());
+        let sink = FileSink::new(
+            logfile,
+            WriteMode::BufferedAsync {
+                flush_interval: Duration::from_secs(60),
+            },
+        );
+        sink.write(b"hello\n");
+        sink.flush();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        sink.shutdown();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(contents, "hello\n");
+    }
+}